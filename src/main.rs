@@ -1,17 +1,34 @@
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::FileExt;
 use std::path::Path;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use clap::Parser;
+use crossbeam_channel::unbounded;
 
 const PRIMES_CSV: &str = "primes.csv";
 const PRIMES_BIN: &str = "primes.bin";
+const TIMINGS_CSV: &str = "timings.csv";
+const CHECKPOINT_FILE: &str = "sieve.ckpt";
 
 // Default values for command line options
 const DEFAULT_WINDOW_SIZE: u32 = 100_000;
 const DEFAULT_UPPER_LIMIT: u64 = 1_000_000;
 
+// On-disk framing for primes.bin: a fixed 8-byte header (4-byte magic, 1-byte
+// version, 3 reserved bytes) followed by little-endian `Prime` records. The
+// little-endian encoding makes the file portable across byte orders; the header
+// lets us reject foreign files and migrate legacy headerless ones. A file shorter
+// than the header, or without the magic and not a whole number of records, is
+// rejected; a magic-less record-aligned file is taken for a legacy dump. A
+// partial record after a valid header is not rejected: reads stop before it.
+const MAGIC: [u8; 4] = *b"SIEV";
+const FORMAT_VERSION: u8 = 1;
+const HEADER_SIZE: usize = 8;
+const PRIME_DATA_START: u64 = HEADER_SIZE as u64;
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -26,6 +43,18 @@ struct Args {
 
     #[arg(short, long)]
     fast: bool,
+
+    #[arg(long)]
+    gpu: bool,
+
+    #[arg(long)]
+    timings: bool,
+
+    #[arg(short = 'j', long, default_value_t = 1)]
+    jobs: usize,
+
+    #[arg(long)]
+    resume: bool,
 }
 
 #[derive(Clone, Copy)]
@@ -38,20 +67,114 @@ impl Prime {
     const SIZE: usize = std::mem::size_of::<Prime>();
 }
 
+// One row of per-window profiling, broken down into the four phases of a
+// window pass so the I/O-vs-compute split shows up directly in `timings.csv`.
+struct BenchStatistics {
+    window_index: u64,
+    read_us: u128,
+    mark_us: u128,
+    discover_us: u128,
+    write_us: u128,
+    primes_found: u64,
+}
+
+fn timings_write(stats: &[BenchStatistics]) -> io::Result<()> {
+    let output = File::create(TIMINGS_CSV)?;
+    let mut writer = BufWriter::new(output);
+    writeln!(
+        writer,
+        "window_index,read_us,mark_us,discover_us,write_us,primes_found"
+    )?;
+    for row in stats {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{}",
+            row.window_index,
+            row.read_us,
+            row.mark_us,
+            row.discover_us,
+            row.write_us,
+            row.primes_found
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn header_bytes() -> [u8; HEADER_SIZE] {
+    let mut header = [0u8; HEADER_SIZE];
+    header[0..4].copy_from_slice(&MAGIC);
+    header[4] = FORMAT_VERSION;
+    header
+}
+
+// Rewrite a headerless legacy file in place, prepending the current header so
+// subsequent opens see a well-framed, self-describing file. The record data is
+// shifted forward by the header size in fixed-size chunks from the tail, so a
+// multi-gigabyte 10^12 table migrates without being buffered whole.
+fn migrate_legacy(file: &mut File, len: u64) -> io::Result<()> {
+    const CHUNK: u64 = 1 << 20;
+    file.set_len(len + HEADER_SIZE as u64)?;
+    let mut buf = vec![0u8; CHUNK as usize];
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = remaining.min(CHUNK);
+        let start = remaining - n;
+        let slice = &mut buf[..n as usize];
+        file.read_exact_at(slice, start)?;
+        file.write_all_at(slice, start + HEADER_SIZE as u64)?;
+        remaining = start;
+    }
+    file.write_all_at(&header_bytes(), 0)?;
+    file.flush()?;
+    Ok(())
+}
+
+// Open primes.bin, creating and stamping a header for a fresh file, validating
+// it for an existing one, and upgrading a legacy headerless file in place. On
+// return the cursor sits at the first record.
 fn prime_open(filename: &str) -> io::Result<File> {
-    OpenOptions::new()
+    let mut file = OpenOptions::new()
         .read(true)
         .write(true)
         .create(true)
-        .open(filename)
+        .truncate(false)
+        .open(filename)?;
+
+    let len = file.metadata()?.len();
+    if len == 0 {
+        file.write_all(&header_bytes())?;
+    } else {
+        let mut header = [0u8; HEADER_SIZE];
+        file.read_exact(&mut header)?;
+        if header[0..4] != MAGIC {
+            // No magic: treat a record-aligned file as a legacy dump and upgrade.
+            if len % Prime::SIZE as u64 == 0 {
+                migrate_legacy(&mut file, len)?;
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "primes.bin: bad magic and not a legacy file",
+                ));
+            }
+        } else if header[4] != FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("primes.bin: unsupported version {}", header[4]),
+            ));
+        }
+    }
+
+    file.seek(SeekFrom::Start(PRIME_DATA_START))?;
+    Ok(file)
 }
 
 fn prime_read(file: &mut File, prime: &mut Prime) -> io::Result<bool> {
     let mut buf = [0u8; std::mem::size_of::<Prime>()];
     match file.read_exact(&mut buf) {
         Ok(_) => {
-            let p = u64::from_ne_bytes(buf[0..8].try_into().unwrap());
-            let nextval = u64::from_ne_bytes(buf[8..16].try_into().unwrap());
+            let p = u64::from_le_bytes(buf[0..8].try_into().unwrap());
+            let nextval = u64::from_le_bytes(buf[8..16].try_into().unwrap());
             *prime = Prime { p, nextval };
             Ok(true)
         }
@@ -62,8 +185,8 @@ fn prime_read(file: &mut File, prime: &mut Prime) -> io::Result<bool> {
 
 fn prime_write(file: &mut File, prime: &Prime) -> io::Result<()> {
     let mut buf = Vec::with_capacity(Prime::SIZE);
-    buf.extend_from_slice(&prime.p.to_ne_bytes());
-    buf.extend_from_slice(&prime.nextval.to_ne_bytes());
+    buf.extend_from_slice(&prime.p.to_le_bytes());
+    buf.extend_from_slice(&prime.nextval.to_le_bytes());
     file.write_all(&buf)
 }
 
@@ -72,6 +195,115 @@ fn prime_unread(file: &mut File) -> io::Result<()> {
     Ok(())
 }
 
+// OpenCL kernel: one work-item per known prime. Each item starts at its own
+// `nextval`, converts it to a window-relative index, then strides by `p`
+// writing 0 (composite) into the output byte array until it walks off the
+// window. The final `nextval` is written back so persistence survives.
+#[cfg(feature = "gpu")]
+const CHECK_PRIME_SRC: &str = r#"
+__kernel void check_prime(
+    __global const ulong *p,
+    __global ulong *nextval,
+    __global uchar *is_prime,
+    const ulong window_base,
+    const ulong window_size)
+{
+    size_t i = get_global_id(0);
+    ulong stride = p[i];
+    ulong cur = nextval[i];
+    while (cur < window_base + window_size) {
+        is_prime[cur - window_base] = 0;
+        cur += stride;
+    }
+    nextval[i] = cur;
+}
+"#;
+
+// Offload the composite-marking step for a single window to the GPU. `primes`
+// is updated in place with the advanced `nextval` for each prime so the caller
+// can persist the new offsets exactly as the CPU path does.
+#[cfg(feature = "gpu")]
+fn mark_composites_gpu(
+    is_prime: &mut [u8],
+    primes: &mut [Prime],
+    window_base: u64,
+    window_size: u64,
+) -> io::Result<()> {
+    use ocl::ProQue;
+
+    if primes.is_empty() {
+        return Ok(());
+    }
+
+    let p_vals: Vec<u64> = primes.iter().map(|cp| cp.p).collect();
+    let mut nextvals: Vec<u64> = primes.iter().map(|cp| cp.nextval).collect();
+
+    let pro_que = ProQue::builder()
+        .src(CHECK_PRIME_SRC)
+        .dims(primes.len())
+        .build()
+        .map_err(|e| io::Error::other(e.to_string()))?;
+
+    let to_io = |e: ocl::Error| io::Error::other(e.to_string());
+
+    let p_buf = pro_que
+        .buffer_builder::<u64>()
+        .copy_host_slice(&p_vals)
+        .build()
+        .map_err(to_io)?;
+    let nextval_buf = pro_que
+        .buffer_builder::<u64>()
+        .copy_host_slice(&nextvals)
+        .build()
+        .map_err(to_io)?;
+    let is_prime_buf = pro_que
+        .buffer_builder::<u8>()
+        .len(is_prime.len())
+        .copy_host_slice(is_prime)
+        .build()
+        .map_err(to_io)?;
+
+    let kernel = pro_que
+        .kernel_builder("check_prime")
+        .arg(&p_buf)
+        .arg(&nextval_buf)
+        .arg(&is_prime_buf)
+        .arg(window_base)
+        .arg(window_size)
+        .build()
+        .map_err(to_io)?;
+
+    unsafe {
+        kernel.enq().map_err(to_io)?;
+    }
+
+    is_prime_buf.read(is_prime).enq().map_err(to_io)?;
+    nextval_buf.read(&mut nextvals).enq().map_err(to_io)?;
+
+    for (cp, &nv) in primes.iter_mut().zip(nextvals.iter()) {
+        cp.nextval = nv;
+    }
+    Ok(())
+}
+
+// Fallback when the crate is built without the `gpu` feature: mark on the CPU
+// using the same stride loop so `--gpu` degrades gracefully to the default path.
+#[cfg(not(feature = "gpu"))]
+fn mark_composites_gpu(
+    is_prime: &mut [u8],
+    primes: &mut [Prime],
+    window_base: u64,
+    window_size: u64,
+) -> io::Result<()> {
+    for cp in primes.iter_mut() {
+        while cp.nextval < window_base + window_size {
+            is_prime[(cp.nextval - window_base) as usize] = 0;
+            cp.nextval += cp.p;
+        }
+    }
+    Ok(())
+}
+
 fn prime_bin2csv(input_name: &str, output_name: &str, fast: bool) -> io::Result<usize> {
     let mut input = prime_open(input_name)?;
     let output = File::create(output_name)?;
@@ -90,54 +322,140 @@ fn prime_bin2csv(input_name: &str, output_name: &str, fast: bool) -> io::Result<
     Ok(count)
 }
 
-fn sieve(buffer_size: u32, upper_limit: u64, fast: bool, verbose: bool) -> io::Result<()> {
+fn sieve(
+    buffer_size: u32,
+    upper_limit: u64,
+    fast: bool,
+    verbose: bool,
+    gpu: bool,
+    timings: bool,
+    resume: bool,
+) -> io::Result<()> {
+    let bsz = u64::from(buffer_size);
     let mut current_window: u64 = 0;
+    if resume {
+        let inferred = resume_infer(buffer_size)?;
+        current_window = match checkpoint_read()? {
+            // A checkpoint far ahead of what primes.bin actually contains means
+            // the two files are out of sync (e.g. primes.bin is missing or was
+            // truncated underneath an existing sieve.ckpt); trust the table over
+            // the sidecar rather than resuming with zero base primes.
+            Some((cw, _)) if cw <= inferred + bsz => cw,
+            Some((cw, _)) => {
+                if verbose {
+                    eprintln!(
+                        "checkpoint window {} inconsistent with primes.bin (inferred {}); falling back",
+                        cw, inferred
+                    );
+                }
+                inferred
+            }
+            None => inferred,
+        };
+        resume_truncate(current_window)?;
+        if verbose {
+            eprintln!("resuming at window {}", current_window);
+        }
+    }
+    let mut window_index: u64 = current_window / bsz;
     let mut is_prime = vec![true; buffer_size as usize];
     let mut fp = prime_open(PRIMES_BIN)?;
-    
+    let record = verbose || timings;
+    let mut stats: Vec<BenchStatistics> = Vec::new();
+
     while current_window < upper_limit {
         if verbose {
             eprintln!("current_window: {}", current_window);
         }
-        
+
         is_prime.fill(true);
         let mut cp = Prime { p: 0, nextval: 0 };
-        
+        let mut read_dur = Duration::ZERO;
+        let mut mark_dur = Duration::ZERO;
+        let mut write_dur = Duration::ZERO;
+
         // Mark composites from known primes
-        while prime_read(&mut fp, &mut cp)? {
-            let mut entered_loop = false;
-            
-            while cp.nextval < current_window + u64::from(buffer_size) {
-                entered_loop = true;
-                let val = (cp.nextval - current_window) as usize;
-                if val < buffer_size as usize {
-                    if !fast && cp.nextval % 1_000_000 == 0 {
-                        thread::sleep(Duration::from_millis(150));
+        if gpu {
+            // Slurp the whole prime table, mark on the device, then rewrite the
+            // advanced `nextval`s in place. Records are fixed size and in order,
+            // so a single rewrite from the first record (just past the header)
+            // reproduces the CPU path exactly.
+            let read_start = Instant::now();
+            let mut primes = Vec::new();
+            while prime_read(&mut fp, &mut cp)? {
+                primes.push(cp);
+            }
+            read_dur += read_start.elapsed();
+
+            let mark_start = Instant::now();
+            let mut marks = vec![1u8; buffer_size as usize];
+            mark_composites_gpu(
+                &mut marks,
+                &mut primes,
+                current_window,
+                u64::from(buffer_size),
+            )?;
+            for (slot, &mark) in is_prime.iter_mut().zip(marks.iter()) {
+                *slot = mark != 0;
+            }
+            mark_dur += mark_start.elapsed();
+
+            let write_start = Instant::now();
+            fp.seek(SeekFrom::Start(PRIME_DATA_START))?;
+            for cp in &primes {
+                prime_write(&mut fp, cp)?;
+            }
+            write_dur += write_start.elapsed();
+        } else {
+            loop {
+                let read_start = Instant::now();
+                let more = prime_read(&mut fp, &mut cp)?;
+                read_dur += read_start.elapsed();
+                if !more {
+                    break;
+                }
+
+                let mark_start = Instant::now();
+                let mut entered_loop = false;
+                while cp.nextval < current_window + u64::from(buffer_size) {
+                    entered_loop = true;
+                    let val = (cp.nextval - current_window) as usize;
+                    if val < buffer_size as usize {
+                        if !fast && cp.nextval.is_multiple_of(1_000_000) {
+                            thread::sleep(Duration::from_millis(150));
+                        }
+                        is_prime[val] = false;
                     }
-                    is_prime[val] = false;
+                    cp.nextval += cp.p;
+                }
+                mark_dur += mark_start.elapsed();
+
+                if entered_loop {
+                    let write_start = Instant::now();
+                    prime_unread(&mut fp)?;
+                    prime_write(&mut fp, &cp)?;
+                    write_dur += write_start.elapsed();
                 }
-                cp.nextval += cp.p;
-            }
-            
-            if entered_loop {
-                prime_unread(&mut fp)?;
-                prime_write(&mut fp, &cp)?;
             }
         }
-        
+
         // Discover new primes
+        let discover_start = Instant::now();
+        let mut primes_found: u64 = 0;
         let start_p = if current_window == 0 { 2 } else { current_window };
-        for potential_prime in start_p..current_window + u64::from(buffer_size) {
+        let discover_end = (current_window + u64::from(buffer_size)).min(upper_limit);
+        for potential_prime in start_p..discover_end {
             let val = (potential_prime - current_window) as usize;
             if val < buffer_size as usize && is_prime[val] {
                 cp.p = potential_prime;
                 cp.nextval = cp.p + cp.p;
-                
+                primes_found += 1;
+
                 // Mark multiples as not prime
                 while cp.nextval < current_window + u64::from(buffer_size) {
                     let val = (cp.nextval - current_window) as usize;
                     if val < buffer_size as usize {
-                        if !fast && cp.nextval % 100_000 == 0 {
+                        if !fast && cp.nextval.is_multiple_of(100_000) {
                             thread::sleep(Duration::from_millis(150));
                         }
                         is_prime[val] = false;
@@ -147,16 +465,262 @@ fn sieve(buffer_size: u32, upper_limit: u64, fast: bool, verbose: bool) -> io::R
                 prime_write(&mut fp, &cp)?;
             }
         }
-        
-        fp.seek(SeekFrom::Start(0))?;
-        current_window += u64::from(buffer_size);
+        let discover_dur = discover_start.elapsed();
+
+        if record {
+            stats.push(BenchStatistics {
+                window_index,
+                read_us: read_dur.as_micros(),
+                mark_us: mark_dur.as_micros(),
+                discover_us: discover_dur.as_micros(),
+                write_us: write_dur.as_micros(),
+                primes_found,
+            });
+        }
+
+        fp.seek(SeekFrom::Start(PRIME_DATA_START))?;
+        current_window += bsz;
+        window_index += 1;
+        checkpoint_write(current_window, upper_limit)?;
+    }
+
+    if record {
+        timings_write(&stats)?;
+    }
+
+    fp.flush()?;
+    checkpoint_remove()?;
+    Ok(())
+}
+
+// Integer square root, used to decide how far the base-prime table must reach
+// before windows can be sieved independently.
+fn isqrt(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+// Sieve a single window in isolation given the shared base-prime `p` values.
+// Each worker keeps its own `nextval` per prime here, derived from the window
+// base, so no shared cursor or file state is touched. Returns the primes newly
+// discovered inside the window, framed as persistable `Prime` records.
+fn sieve_window(
+    bases: &[u64],
+    window_base: u64,
+    buffer_size: u32,
+    upper_limit: u64,
+    fast: bool,
+) -> Vec<Prime> {
+    let bsz = u64::from(buffer_size);
+    let window_end = window_base + bsz;
+    let mut is_prime = vec![true; buffer_size as usize];
+
+    for &p in bases {
+        // First multiple of `p` that is at least `2*p` and lands in the window.
+        let mut nextval = p + p;
+        if nextval < window_base {
+            nextval = window_base.div_ceil(p) * p;
+        }
+        while nextval < window_end {
+            if !fast && nextval.is_multiple_of(1_000_000) {
+                thread::sleep(Duration::from_millis(150));
+            }
+            is_prime[(nextval - window_base) as usize] = false;
+            nextval += p;
+        }
+    }
+
+    let mut found = Vec::new();
+    for potential_prime in window_base..window_end {
+        if potential_prime >= upper_limit {
+            break;
+        }
+        let val = (potential_prime - window_base) as usize;
+        if is_prime[val] {
+            // These lie above sqrt(upper_limit), so their smallest multiple
+            // already exceeds the range; nothing left to mark in-window.
+            found.push(Prime {
+                p: potential_prime,
+                nextval: potential_prime + potential_prime,
+            });
+        }
+    }
+    found
+}
+
+// Producer/consumer sieve: a pool of `jobs` workers each take distinct windows
+// off a shared queue and sieve them in parallel against the base-prime table
+// (read positionally via `read_exact_at`), streaming discovered primes back to
+// a single writer that appends them to `primes.bin` in window order.
+fn sieve_parallel(
+    buffer_size: u32,
+    upper_limit: u64,
+    jobs: usize,
+    fast: bool,
+    verbose: bool,
+) -> io::Result<()> {
+    let bsz = u64::from(buffer_size);
+
+    // Phase 1: establish every base prime up to sqrt(upper_limit) on the single
+    // cursor path, rounded up to a window boundary so parallel windows start clean.
+    let base_limit = isqrt(upper_limit) + 1;
+    let base_boundary = base_limit.div_ceil(bsz) * bsz;
+    let base_boundary = base_boundary.min(upper_limit);
+    sieve(buffer_size, base_boundary, fast, verbose, false, false, false)?;
+    if base_boundary >= upper_limit {
+        return Ok(());
+    }
+
+    // Load the base `p` values once per worker via positional reads.
+    let base_bytes = std::fs::metadata(PRIMES_BIN)?.len();
+    let base_count = ((base_bytes - PRIME_DATA_START) as usize) / Prime::SIZE;
+
+    let (job_tx, job_rx) = unbounded::<u64>();
+    let (out_tx, out_rx) = unbounded::<(u64, Vec<Prime>)>();
+
+    let mut wbase = base_boundary;
+    while wbase < upper_limit {
+        job_tx.send(wbase).expect("job queue closed");
+        wbase += bsz;
+    }
+    drop(job_tx);
+
+    let mut workers = Vec::with_capacity(jobs);
+    for _ in 0..jobs {
+        let job_rx = job_rx.clone();
+        let out_tx = out_tx.clone();
+        workers.push(thread::spawn(move || -> io::Result<()> {
+            let reader = OpenOptions::new().read(true).open(PRIMES_BIN)?;
+            let mut bases = Vec::with_capacity(base_count);
+            let mut buf = [0u8; Prime::SIZE];
+            for i in 0..base_count {
+                reader.read_exact_at(&mut buf, PRIME_DATA_START + (i * Prime::SIZE) as u64)?;
+                bases.push(u64::from_le_bytes(buf[0..8].try_into().unwrap()));
+            }
+            while let Ok(window_base) = job_rx.recv() {
+                let found = sieve_window(&bases, window_base, buffer_size, upper_limit, fast);
+                if out_tx.send((window_base, found)).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        }));
+    }
+    drop(out_tx);
+
+    // Single writer: drain `out_rx` as results arrive, holding only the windows
+    // that finished out of order in `pending`, and append each window's primes
+    // to primes.bin as soon as it becomes the next one due — never materializing
+    // more than the in-flight windows at once.
+    let mut pending: HashMap<u64, Vec<Prime>> = HashMap::new();
+    let mut next_window = base_boundary;
+
+    let mut fp = prime_open(PRIMES_BIN)?;
+    fp.seek(SeekFrom::End(0))?;
+    for (window_base, found) in out_rx.iter() {
+        pending.insert(window_base, found);
+        while let Some(primes) = pending.remove(&next_window) {
+            for prime in &primes {
+                prime_write(&mut fp, prime)?;
+            }
+            next_window += bsz;
+        }
+    }
+    fp.flush()?;
+
+    for worker in workers {
+        match worker.join() {
+            Ok(result) => result?,
+            Err(_) => return Err(io::Error::other("worker thread panicked")),
+        }
+    }
+    Ok(())
+}
+
+// Persist the next window boundary still to be sieved, plus the run's upper
+// limit, to a small sidecar so an interrupted run can pick up where it stopped.
+fn checkpoint_write(current_window: u64, upper_limit: u64) -> io::Result<()> {
+    let mut file = File::create(CHECKPOINT_FILE)?;
+    file.write_all(&current_window.to_le_bytes())?;
+    file.write_all(&upper_limit.to_le_bytes())?;
+    file.flush()
+}
+
+fn checkpoint_read() -> io::Result<Option<(u64, u64)>> {
+    match File::open(CHECKPOINT_FILE) {
+        Ok(mut file) => {
+            let mut buf = [0u8; 16];
+            file.read_exact(&mut buf)?;
+            let current_window = u64::from_le_bytes(buf[0..8].try_into().unwrap());
+            let upper_limit = u64::from_le_bytes(buf[8..16].try_into().unwrap());
+            Ok(Some((current_window, upper_limit)))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn checkpoint_remove() -> io::Result<()> {
+    if Path::new(CHECKPOINT_FILE).exists() {
+        std::fs::remove_file(CHECKPOINT_FILE)?;
+    }
+    Ok(())
+}
+
+// Infer the resume boundary from primes.bin alone when no checkpoint survives:
+// the largest stored `p` fixes the last window that produced output, so re-enter
+// at that window's base and redo it.
+fn resume_infer(buffer_size: u32) -> io::Result<u64> {
+    let mut fp = prime_open(PRIMES_BIN)?;
+    let mut cp = Prime { p: 0, nextval: 0 };
+    let mut max_p = 0u64;
+    while prime_read(&mut fp, &mut cp)? {
+        if cp.p > max_p {
+            max_p = cp.p;
+        }
+    }
+    let bsz = u64::from(buffer_size);
+    Ok((max_p / bsz) * bsz)
+}
+
+// Drop every prime discovered at or beyond `start_window` so a resumed run can
+// recompute those windows from scratch without leaving duplicate records behind.
+// The stored `nextval` of a kept prime may have been advanced past the resume
+// boundary by the interrupted window, so reset it to that prime's first multiple
+// at or beyond `start_window`; trusting the file's value would skip marking and
+// report composites as primes.
+fn resume_truncate(start_window: u64) -> io::Result<()> {
+    let mut fp = prime_open(PRIMES_BIN)?;
+    let mut cp = Prime { p: 0, nextval: 0 };
+    let mut kept = Vec::new();
+    while prime_read(&mut fp, &mut cp)? {
+        if cp.p < start_window {
+            let mut nextval = cp.p + cp.p;
+            if nextval < start_window {
+                nextval = start_window.div_ceil(cp.p) * cp.p;
+            }
+            kept.push(Prime { p: cp.p, nextval });
+        }
     }
-    
+    fp.seek(SeekFrom::Start(PRIME_DATA_START))?;
+    for prime in &kept {
+        prime_write(&mut fp, prime)?;
+    }
+    fp.set_len(PRIME_DATA_START + (kept.len() * Prime::SIZE) as u64)?;
     fp.flush()?;
     Ok(())
 }
 
 fn files_remove() -> io::Result<()> {
+    checkpoint_remove()?;
     for file in &[PRIMES_BIN, PRIMES_CSV] {
         if Path::new(file).exists() {
             std::fs::remove_file(file)?;
@@ -172,8 +736,43 @@ fn main() -> io::Result<()> {
     println!("Window size: {}", args.window_size);
     println!("Upper limit: {}", args.upper_limit);
 
-    files_remove()?;
-    sieve(args.window_size, args.upper_limit, args.fast, args.verbose)?;
+    // The parallel path does not support these flags; refuse rather than
+    // silently re-sieving from zero over a retained file (which would duplicate
+    // every record) or dropping the requested GPU marking.
+    if args.jobs > 1 && args.resume {
+        return Err(io::Error::other("--resume is not supported with -j/--jobs > 1"));
+    }
+    if args.jobs > 1 && args.gpu {
+        return Err(io::Error::other("--gpu is not supported with -j/--jobs > 1"));
+    }
+    // Per-window BenchStatistics are collected only on the single-cursor path, so
+    // --timings would silently produce no timings.csv under parallelism.
+    if args.jobs > 1 && args.timings {
+        return Err(io::Error::other("--timings is not supported with -j/--jobs > 1"));
+    }
+
+    if !args.resume {
+        files_remove()?;
+    }
+    if args.jobs > 1 {
+        sieve_parallel(
+            args.window_size,
+            args.upper_limit,
+            args.jobs,
+            args.fast,
+            args.verbose,
+        )?;
+    } else {
+        sieve(
+            args.window_size,
+            args.upper_limit,
+            args.fast,
+            args.verbose,
+            args.gpu,
+            args.timings,
+            args.resume,
+        )?;
+    }
     let count = prime_bin2csv(PRIMES_BIN, PRIMES_CSV, args.fast)?;
     println!("Found {} primes", count);
 